@@ -1,7 +1,11 @@
 use super::Result;
+use std::collections::VecDeque;
 use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::{Child, ChildStdout, Command};
+use tokio::sync::mpsc;
+use tokio::time::sleep;
 
 pub fn logs(kubectl_command: &str, namespace: &str, resource: &str) -> Result<Reader> {
     let mut command = Command::new(kubectl_command);
@@ -66,6 +70,289 @@ impl Reader {
     }
 }
 
+/// Number of recently-emitted line hashes remembered across a restart, used
+/// to drop duplicate lines that straddle the restart boundary.
+const DEDUPE_WINDOW: usize = 32;
+
+/// Upper bound on the exponential backoff between respawn attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Bounded channel capacity for `Supervisor`'s line stream. Once full, the
+/// supervisor stops reading from the child until the consumer drains it,
+/// applying backpressure instead of buffering an unbounded backlog.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Builds the `kubectl logs -f` command used by `Supervisor`, requesting
+/// `--timestamps` (so restarts can resume from the last emitted line) and,
+/// on a restart, `--since-time` to avoid losing the window covered by the
+/// previous process.
+fn build_supervised_command(
+    kubectl_command: &str,
+    namespace: &str,
+    resource: &str,
+    since_time: Option<&str>,
+) -> Command {
+    let mut command = Command::new(kubectl_command);
+
+    command.stdin(Stdio::null()).stderr(Stdio::inherit());
+
+    command.arg("logs");
+    command.arg("-f");
+    command.arg("--timestamps");
+    command.arg("-n").arg(namespace);
+    if let Some(since_time) = since_time {
+        command.arg("--since-time").arg(since_time);
+    }
+    command.arg(resource);
+
+    command
+}
+
+/// Spawns a `Supervisor` that keeps a `kubectl logs -f` stream alive across
+/// pod churn and transient API-server disconnects.
+pub fn supervised_logs(kubectl_command: &str, namespace: &str, resource: &str) -> Supervisor {
+    Supervisor::spawn(
+        kubectl_command.to_owned(),
+        namespace.to_owned(),
+        resource.to_owned(),
+    )
+}
+
+/// A supervised, auto-restarting `kubectl logs -f` stream.
+///
+/// Unlike `Reader`, `Supervisor` treats the subprocess as disposable: if it
+/// exits, or its stdout reaches EOF unexpectedly (both routine across pod
+/// restarts and API-server disconnects), it respawns `kubectl logs -f` with
+/// exponential backoff, passing `--since-time` set to the timestamp of the
+/// last line emitted so no window of logs is lost. Duplicate lines that
+/// straddle the restart boundary are suppressed by remembering the last
+/// `DEDUPE_WINDOW` line hashes. Lines are delivered over a bounded channel,
+/// so a slow consumer applies backpressure instead of the supervisor
+/// buffering an unbounded backlog in memory.
+pub struct Supervisor {
+    lines: mpsc::Receiver<String>,
+    stop: mpsc::Sender<()>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Supervisor {
+    fn spawn(kubectl_command: String, namespace: String, resource: String) -> Self {
+        let (lines_tx, lines_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (stop_tx, stop_rx) = mpsc::channel(1);
+
+        let task = tokio::spawn(Self::run(
+            kubectl_command,
+            namespace,
+            resource,
+            lines_tx,
+            stop_rx,
+        ));
+
+        Self {
+            lines: lines_rx,
+            stop: stop_tx,
+            task,
+        }
+    }
+
+    /// Receives the next log line, or `None` once the supervisor has been
+    /// stopped and its channel drained.
+    pub async fn recv(&mut self) -> Option<String> {
+        self.lines.recv().await
+    }
+
+    /// Cancels the supervisor: no further respawn is attempted and the
+    /// current child, if any, is killed.
+    pub async fn stop(self) {
+        let Self { lines, stop, task } = self;
+        let _ = stop.send(()).await;
+        // Drop the receiver before awaiting the task: if `run` is currently
+        // parked on `lines_tx.send(...)` for some other reason, closing the
+        // channel from this end unblocks it instead of deadlocking here.
+        drop(lines);
+        let _ = task.await;
+    }
+
+    async fn run(
+        kubectl_command: String,
+        namespace: String,
+        resource: String,
+        lines_tx: mpsc::Sender<String>,
+        mut stop_rx: mpsc::Receiver<()>,
+    ) {
+        let mut since_time: Option<String> = None;
+        let mut recent_hashes: VecDeque<u64> = VecDeque::with_capacity(DEDUPE_WINDOW);
+        let mut attempt: u32 = 0;
+
+        loop {
+            // The `--since-time` this spawn is resuming from, if any; fixed
+            // for the lifetime of this reader so the dedup window below can
+            // tell "a line from before the restart" apart from "a line that
+            // happens to repeat during steady-state streaming."
+            let resume_since = since_time.clone();
+
+            let command = build_supervised_command(
+                &kubectl_command,
+                &namespace,
+                &resource,
+                resume_since.as_deref(),
+            );
+
+            let mut reader = match Reader::spawn(command) {
+                Ok(reader) => reader,
+                Err(_) => {
+                    if Self::backoff_or_stop(&mut attempt, &mut stop_rx).await {
+                        return;
+                    }
+                    continue;
+                }
+            };
+            attempt = 0;
+
+            // `recent_hashes` only guards against duplicates re-emitted from
+            // before a restart; a fresh (non-restart) spawn has no prior
+            // window to straddle. Once a post-restart line's own timestamp
+            // moves past `resume_since`, we're reading genuinely new output
+            // and stop consulting it, so two legitimately-identical lines
+            // emitted back to back during steady-state aren't swallowed.
+            let mut past_restart_boundary = resume_since.is_none();
+
+            loop {
+                tokio::select! {
+                    _ = stop_rx.recv() => {
+                        let _ = reader.kill();
+                        return;
+                    }
+                    line = reader.read_line() => {
+                        match line {
+                            Some(line) => {
+                                let hash = hash_line(&line);
+                                if !past_restart_boundary && recent_hashes.contains(&hash) {
+                                    continue;
+                                }
+                                if recent_hashes.len() == DEDUPE_WINDOW {
+                                    recent_hashes.pop_front();
+                                }
+                                recent_hashes.push_back(hash);
+
+                                if let Some(timestamp) = leading_timestamp(&line) {
+                                    if !past_restart_boundary {
+                                        if let Some(resume_since) = resume_since.as_deref() {
+                                            if timestamp.as_str() > resume_since {
+                                                past_restart_boundary = true;
+                                            }
+                                        }
+                                    }
+                                    since_time = Some(timestamp);
+                                }
+
+                                // Race the send against `stop_rx` too: if the
+                                // consumer is stalled and the channel is full,
+                                // a plain `.await` here would never observe a
+                                // concurrent `stop()`.
+                                tokio::select! {
+                                    _ = stop_rx.recv() => {
+                                        let _ = reader.kill();
+                                        return;
+                                    }
+                                    result = lines_tx.send(line) => {
+                                        if result.is_err() {
+                                            // Consumer is gone; nothing left to supervise.
+                                            let _ = reader.kill();
+                                            return;
+                                        }
+                                    }
+                                }
+                            }
+                            None => break, // EOF: fall through and respawn.
+                        }
+                    }
+                }
+            }
+
+            let _ = reader.wait().await;
+
+            if Self::backoff_or_stop(&mut attempt, &mut stop_rx).await {
+                return;
+            }
+        }
+    }
+
+    /// Sleeps for a capped, jittered exponential backoff before the next
+    /// respawn attempt. Returns `true` if `stop()` was called meanwhile.
+    async fn backoff_or_stop(attempt: &mut u32, stop_rx: &mut mpsc::Receiver<()>) -> bool {
+        let backoff = exponential_backoff(*attempt);
+        *attempt += 1;
+
+        tokio::select! {
+            _ = stop_rx.recv() => true,
+            _ = sleep(backoff) => false,
+        }
+    }
+}
+
+fn exponential_backoff(attempt: u32) -> Duration {
+    let base_ms = 200u64.saturating_mul(1u64 << attempt.min(8));
+    let capped = Duration::from_millis(base_ms).min(MAX_BACKOFF);
+    let jitter = Duration::from_millis(rand::random::<u64>() % 250);
+    capped + jitter
+}
+
+fn hash_line(line: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    line.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Best-effort extraction of the leading RFC3339 timestamp written by
+/// `kubectl logs --timestamps`, used to resume with `--since-time` after a
+/// restart.
+///
+/// Returns `None` if the line's first token doesn't look like a timestamp,
+/// rather than passing a garbage token through: handing kubectl a
+/// non-timestamp `--since-time` makes it reject the command outright, and
+/// since a successful `spawn` resets the backoff `attempt` counter to zero,
+/// an unvalidated token would make the supervisor busy-respawn at the
+/// backoff floor instead of escalating.
+fn leading_timestamp(line: &str) -> Option<String> {
+    let token = line.split_whitespace().next()?;
+    if looks_like_rfc3339(token) {
+        Some(token.to_owned())
+    } else {
+        None
+    }
+}
+
+/// Structural (not calendar-accurate) check that `token` has the shape of an
+/// RFC3339 timestamp, e.g. `2021-01-01T00:00:00.000000000Z`.
+fn looks_like_rfc3339(token: &str) -> bool {
+    let bytes = token.as_bytes();
+    let digit = |i: usize| bytes.get(i).map_or(false, u8::is_ascii_digit);
+
+    bytes.len() >= 20
+        && digit(0)
+        && digit(1)
+        && digit(2)
+        && digit(3)
+        && bytes[4] == b'-'
+        && digit(5)
+        && digit(6)
+        && bytes[7] == b'-'
+        && digit(8)
+        && digit(9)
+        && bytes[10] == b'T'
+        && digit(11)
+        && digit(12)
+        && bytes[13] == b':'
+        && digit(14)
+        && digit(15)
+        && bytes[16] == b':'
+        && digit(17)
+        && digit(18)
+        && matches!(bytes[19], b'Z' | b'.' | b'+' | b'-')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,4 +413,39 @@ mod tests {
         let exit_status = reader.wait().await.expect("wait failed");
         assert!(!exit_status.success());
     }
+
+    #[test]
+    fn test_exponential_backoff_caps() {
+        let first = exponential_backoff(0);
+        let later = exponential_backoff(100); // would overflow without the cap
+
+        assert!(first < MAX_BACKOFF);
+        assert!(later <= MAX_BACKOFF + Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_leading_timestamp() {
+        let line = "2021-01-01T00:00:00.000000000Z some log line\n";
+        assert_eq!(
+            leading_timestamp(line),
+            Some("2021-01-01T00:00:00.000000000Z".to_owned())
+        );
+        assert_eq!(leading_timestamp(""), None);
+    }
+
+    #[test]
+    fn test_leading_timestamp_rejects_non_timestamp_token() {
+        // A line with no `--timestamps` prefix (or a blank/malformed one)
+        // must not be fed to `--since-time` on the next respawn.
+        assert_eq!(leading_timestamp("some log line with no timestamp\n"), None);
+        assert_eq!(leading_timestamp("2021-01-01 not a timestamp\n"), None);
+    }
+
+    #[test]
+    fn test_looks_like_rfc3339() {
+        assert!(looks_like_rfc3339("2021-01-01T00:00:00.000000000Z"));
+        assert!(looks_like_rfc3339("2021-01-01T00:00:00+01:00"));
+        assert!(!looks_like_rfc3339("not-a-timestamp"));
+        assert!(!looks_like_rfc3339("2021-01-01"));
+    }
 }
@@ -1,34 +1,70 @@
 use super::{Atom, PathComponent, PathIter, Value};
 use std::{collections::BTreeMap, iter::Peekable};
 
+/// How `insert_with` should handle a value that already exists at the target
+/// path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertMode {
+    /// Replace whatever is currently at the path.
+    Overwrite,
+    /// If the existing leaf and the incoming value are both `Value::Map`,
+    /// deep-merge the incoming map into it instead of replacing it.
+    Merge,
+    /// Push the incoming value onto the array at the path, creating the
+    /// array if it doesn't exist yet. Requires a path ending in `[]`.
+    Append,
+}
+
 /// Inserts field value using a path specified using `a.b[1].c` notation.
 pub fn insert(fields: &mut BTreeMap<Atom, Value>, path: &str, value: Value) {
-    map_insert(fields, PathIter::new(path).peekable(), value);
+    insert_with(fields, path, value, InsertMode::Overwrite);
 }
 
-fn map_insert<I>(fields: &mut BTreeMap<Atom, Value>, mut path_iter: Peekable<I>, value: Value)
-where
+/// Inserts `value` at `path`, using `mode` to decide how to handle a value
+/// already present there.
+///
+/// `mode: Append` expects `path` to end in `a.b[]` notation: the array at
+/// `a.b` (created as empty if absent) has `value` pushed onto its end.
+/// `mode: Merge` only changes behavior when the existing leaf and `value`
+/// are both `Value::Map`, in which case they're deep-merged instead of one
+/// replacing the other; otherwise it behaves like `Overwrite`.
+pub fn insert_with(fields: &mut BTreeMap<Atom, Value>, path: &str, value: Value, mode: InsertMode) {
+    if mode == InsertMode::Append {
+        let prefix = path.strip_suffix("[]").unwrap_or(path);
+        map_append(fields, PathIter::new(prefix).peekable(), value);
+        return;
+    }
+
+    map_insert(fields, PathIter::new(path).peekable(), value, mode);
+}
+
+fn map_insert<I>(
+    fields: &mut BTreeMap<Atom, Value>,
+    mut path_iter: Peekable<I>,
+    value: Value,
+    mode: InsertMode,
+) where
     I: Iterator<Item = PathComponent>,
 {
     match (path_iter.next(), path_iter.peek()) {
         (Some(PathComponent::Key(current)), None) => {
-            fields.insert(current, value);
+            insert_leaf(fields, current, value, mode);
         }
         (Some(PathComponent::Key(current)), Some(PathComponent::Key(_))) => {
             if let Some(Value::Map(map)) = fields.get_mut(&current) {
-                map_insert(map, path_iter, value);
+                map_insert(map, path_iter, value, mode);
             } else {
                 let mut map = BTreeMap::new();
-                map_insert(&mut map, path_iter, value);
+                map_insert(&mut map, path_iter, value, mode);
                 fields.insert(current, Value::Map(map));
             }
         }
         (Some(PathComponent::Key(current)), Some(&PathComponent::Index(next))) => {
             if let Some(Value::Array(array)) = fields.get_mut(&current) {
-                array_insert(array, path_iter, value);
+                array_insert(array, path_iter, value, mode);
             } else {
                 let mut array = Vec::with_capacity(next + 1);
-                array_insert(&mut array, path_iter, value);
+                array_insert(&mut array, path_iter, value, mode);
                 fields.insert(current, Value::Array(array));
             }
         }
@@ -36,39 +72,155 @@ where
     }
 }
 
-fn array_insert<I>(values: &mut Vec<Value>, mut path_iter: Peekable<I>, value: Value)
-where
+fn array_insert<I>(
+    values: &mut Vec<Value>,
+    mut path_iter: Peekable<I>,
+    value: Value,
+    mode: InsertMode,
+) where
     I: Iterator<Item = PathComponent>,
 {
     match (path_iter.next(), path_iter.peek()) {
         (Some(PathComponent::Index(current)), None) => {
-            while values.len() < current {
-                values.push(Value::Null);
-            }
-            values.insert(current, value);
+            pad(values, current);
+            insert_leaf_at(values, current, value, mode);
         }
         (Some(PathComponent::Index(current)), Some(PathComponent::Key(_))) => {
             if let Some(Value::Map(map)) = values.get_mut(current) {
-                map_insert(map, path_iter, value);
+                map_insert(map, path_iter, value, mode);
             } else {
                 let mut map = BTreeMap::new();
-                map_insert(&mut map, path_iter, value);
-                while values.len() < current {
-                    values.push(Value::Null);
-                }
-                values.insert(current, Value::Map(map));
+                map_insert(&mut map, path_iter, value, mode);
+                pad(values, current);
+                values[current] = Value::Map(map);
             }
         }
         (Some(PathComponent::Index(current)), Some(PathComponent::Index(next))) => {
             if let Some(Value::Array(array)) = values.get_mut(current) {
-                array_insert(array, path_iter, value);
+                array_insert(array, path_iter, value, mode);
             } else {
                 let mut array = Vec::with_capacity(next + 1);
-                array_insert(&mut array, path_iter, value);
-                while values.len() < current {
-                    values.push(Value::Null);
-                }
-                values.insert(current, Value::Array(array));
+                array_insert(&mut array, path_iter, value, mode);
+                pad(values, current);
+                values[current] = Value::Array(array);
+            }
+        }
+        _ => return,
+    }
+}
+
+/// Pads `values` with `Value::Null` so that index `index` is addressable.
+fn pad(values: &mut Vec<Value>, index: usize) {
+    while values.len() <= index {
+        values.push(Value::Null);
+    }
+}
+
+/// Sets index `index` to `value`, replacing whatever slot is there rather
+/// than shifting later elements (as `Vec::insert` would).
+fn insert_leaf_at(values: &mut Vec<Value>, index: usize, value: Value, mode: InsertMode) {
+    match (mode, values.get_mut(index)) {
+        (InsertMode::Merge, Some(existing @ Value::Map(_))) if matches!(value, Value::Map(_)) => {
+            if let (Value::Map(existing), Value::Map(incoming)) = (existing, value) {
+                deep_merge(existing, incoming);
+            }
+        }
+        _ => values[index] = value,
+    }
+}
+
+fn insert_leaf(fields: &mut BTreeMap<Atom, Value>, key: Atom, value: Value, mode: InsertMode) {
+    match (mode, fields.get_mut(&key)) {
+        (InsertMode::Merge, Some(existing @ Value::Map(_))) if matches!(value, Value::Map(_)) => {
+            if let (Value::Map(existing), Value::Map(incoming)) = (existing, value) {
+                deep_merge(existing, incoming);
+            }
+        }
+        _ => {
+            fields.insert(key, value);
+        }
+    }
+}
+
+/// Recursively merges `incoming` into `target`, without clobbering sibling
+/// keys: a key present in both that holds a map on both sides is merged
+/// recursively, otherwise `incoming`'s value for that key wins.
+fn deep_merge(target: &mut BTreeMap<Atom, Value>, incoming: BTreeMap<Atom, Value>) {
+    for (key, value) in incoming {
+        match (target.get_mut(&key), value) {
+            (Some(Value::Map(existing)), Value::Map(incoming)) => {
+                deep_merge(existing, incoming);
+            }
+            (_, value) => {
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+fn map_append<I>(fields: &mut BTreeMap<Atom, Value>, mut path_iter: Peekable<I>, value: Value)
+where
+    I: Iterator<Item = PathComponent>,
+{
+    match (path_iter.next(), path_iter.peek()) {
+        (Some(PathComponent::Key(current)), None) => match fields.get_mut(&current) {
+            Some(Value::Array(array)) => array.push(value),
+            _ => {
+                fields.insert(current, Value::Array(vec![value]));
+            }
+        },
+        (Some(PathComponent::Key(current)), Some(PathComponent::Key(_))) => {
+            if let Some(Value::Map(map)) = fields.get_mut(&current) {
+                map_append(map, path_iter, value);
+            } else {
+                let mut map = BTreeMap::new();
+                map_append(&mut map, path_iter, value);
+                fields.insert(current, Value::Map(map));
+            }
+        }
+        (Some(PathComponent::Key(current)), Some(&PathComponent::Index(next))) => {
+            if let Some(Value::Array(array)) = fields.get_mut(&current) {
+                array_append(array, path_iter, value);
+            } else {
+                let mut array = Vec::with_capacity(next + 1);
+                array_append(&mut array, path_iter, value);
+                fields.insert(current, Value::Array(array));
+            }
+        }
+        _ => return,
+    }
+}
+
+fn array_append<I>(values: &mut Vec<Value>, mut path_iter: Peekable<I>, value: Value)
+where
+    I: Iterator<Item = PathComponent>,
+{
+    match (path_iter.next(), path_iter.peek()) {
+        (Some(PathComponent::Index(current)), None) => match values.get_mut(current) {
+            Some(Value::Array(array)) => array.push(value),
+            _ => {
+                pad(values, current);
+                values[current] = Value::Array(vec![value]);
+            }
+        },
+        (Some(PathComponent::Index(current)), Some(PathComponent::Key(_))) => {
+            if let Some(Value::Map(map)) = values.get_mut(current) {
+                map_append(map, path_iter, value);
+            } else {
+                let mut map = BTreeMap::new();
+                map_append(&mut map, path_iter, value);
+                pad(values, current);
+                values[current] = Value::Map(map);
+            }
+        }
+        (Some(PathComponent::Index(current)), Some(&PathComponent::Index(next))) => {
+            if let Some(Value::Array(array)) = values.get_mut(current) {
+                array_append(array, path_iter, value);
+            } else {
+                let mut array = Vec::with_capacity(next + 1);
+                array_append(&mut array, path_iter, value);
+                pad(values, current);
+                values[current] = Value::Array(array);
             }
         }
         _ => return,
@@ -111,4 +263,80 @@ mod test {
         }));
         assert_eq!(fields, expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_insert_array_sets_index_without_shifting() {
+        let mut fields = BTreeMap::new();
+        insert(&mut fields, "a[0]".into(), Value::Integer(1));
+        insert(&mut fields, "a[1]".into(), Value::Integer(2));
+        insert(&mut fields, "a[0]".into(), Value::Integer(9));
+
+        let expected = fields_from_json(json!({ "a": [9, 2] }));
+        assert_eq!(fields, expected);
+    }
+
+    #[test]
+    fn test_insert_with_append() {
+        let mut fields = BTreeMap::new();
+        insert_with(
+            &mut fields,
+            "a.b[]".into(),
+            Value::Integer(1),
+            InsertMode::Append,
+        );
+        insert_with(
+            &mut fields,
+            "a.b[]".into(),
+            Value::Integer(2),
+            InsertMode::Append,
+        );
+
+        let expected = fields_from_json(json!({ "a": { "b": [1, 2] } }));
+        assert_eq!(fields, expected);
+    }
+
+    #[test]
+    fn test_insert_with_append_into_nested_array_slot() {
+        let mut fields = BTreeMap::new();
+        insert_with(
+            &mut fields,
+            "a[0][]".into(),
+            Value::Integer(1),
+            InsertMode::Append,
+        );
+        insert_with(
+            &mut fields,
+            "a[0][]".into(),
+            Value::Integer(2),
+            InsertMode::Append,
+        );
+
+        let expected = fields_from_json(json!({ "a": [[1, 2]] }));
+        assert_eq!(fields, expected);
+    }
+
+    #[test]
+    fn test_insert_with_merge() {
+        let mut fields = BTreeMap::new();
+        insert(&mut fields, "a".into(), Value::Map(fields_from_json(json!({ "x": 1 }))));
+        insert_with(
+            &mut fields,
+            "a".into(),
+            Value::Map(fields_from_json(json!({ "y": 2 }))),
+            InsertMode::Merge,
+        );
+
+        let expected = fields_from_json(json!({ "a": { "x": 1, "y": 2 } }));
+        assert_eq!(fields, expected);
+    }
+
+    #[test]
+    fn test_insert_with_merge_overwrites_non_map_leaves() {
+        let mut fields = BTreeMap::new();
+        insert(&mut fields, "a".into(), Value::Integer(1));
+        insert_with(&mut fields, "a".into(), Value::Integer(2), InsertMode::Merge);
+
+        let expected = fields_from_json(json!({ "a": 2 }));
+        assert_eq!(fields, expected);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::event::Value;
+use bytes::{BufMut, BytesMut};
+use serde_cbor::value::Value as CborValue;
+use serde_cbor::Error as CborError;
+use std::collections::BTreeMap;
+use string_cache::DefaultAtom as Atom;
+
+use super::TimestampFormat;
+
+/// CBOR tag 0, the standard tag for an RFC3339 date/time text string.
+const TAG_RFC3339_TIMESTAMP: u64 = 0;
+
+#[derive(Debug)]
+pub enum CborEncodingError {
+    Serialize(CborError),
+}
+
+impl std::fmt::Display for CborEncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Serialize(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CborEncodingError {}
+
+/// Encodes `fields` as a CBOR map into `out`.
+///
+/// Unlike the JSON codec, CBOR keeps `Value::Integer` and `Value::Float`
+/// distinct on the wire (JSON would flatten both to a number) and carries
+/// `Value::Bytes` as a native byte string rather than base64 text, which
+/// matters for binary-safe, high-volume sinks.
+///
+/// `timestamp_format` governs how `Value::Timestamp` is written: `Unix`
+/// (the default) becomes a CBOR integer of whole seconds since the epoch,
+/// while `Rfc3339` is written as a tag-0 (standard date/time) text string.
+pub fn encode_cbor(
+    fields: &BTreeMap<Atom, Value>,
+    out: &mut BytesMut,
+    timestamp_format: Option<TimestampFormat>,
+) -> Result<(), CborEncodingError> {
+    let format = timestamp_format.unwrap_or(TimestampFormat::Unix);
+    let cbor_value = map_to_cbor(fields, format);
+
+    let mut buf = Vec::new();
+    serde_cbor::to_writer(&mut buf, &cbor_value).map_err(CborEncodingError::Serialize)?;
+    out.put_slice(&buf);
+    Ok(())
+}
+
+fn map_to_cbor(fields: &BTreeMap<Atom, Value>, format: TimestampFormat) -> CborValue {
+    let entries = fields
+        .iter()
+        .map(|(key, value)| {
+            (
+                CborValue::Text(key.to_string()),
+                value_to_cbor(value, format),
+            )
+        })
+        .collect();
+    CborValue::Map(entries)
+}
+
+fn value_to_cbor(value: &Value, format: TimestampFormat) -> CborValue {
+    match value {
+        Value::Bytes(bytes) => CborValue::Bytes(bytes.to_vec()),
+        Value::Integer(i) => CborValue::Integer(*i as i128),
+        Value::Float(f) => CborValue::Float(*f),
+        Value::Boolean(b) => CborValue::Bool(*b),
+        Value::Timestamp(timestamp) => match format {
+            TimestampFormat::Unix => CborValue::Integer(timestamp.timestamp() as i128),
+            TimestampFormat::Rfc3339 => CborValue::Tag(
+                TAG_RFC3339_TIMESTAMP,
+                Box::new(CborValue::Text(timestamp.to_rfc3339())),
+            ),
+        },
+        Value::Map(map) => map_to_cbor(map, format),
+        Value::Array(array) => {
+            CborValue::Array(array.iter().map(|v| value_to_cbor(v, format)).collect())
+        }
+        Value::Null => CborValue::Null,
+    }
+}
@@ -1,8 +1,16 @@
+use super::cbor_encoder::CborEncodingError;
+use super::encryption::{BatchKey, EncryptionConfig};
+use super::json_encoder::{
+    encode_streaming, JsonEncodingError, JsonFormat, DEFAULT_JSON_DEPTH_LIMIT,
+};
+use crate::event::Value;
 use crate::sinks::util::encoding::{
     EncodingConfig, EncodingConfiguration, InnerWithDefault, TimestampFormat,
 };
+use bytes::BytesMut;
 use serde::de::{DeserializeOwned, IntoDeserializer, MapAccess, Visitor};
 use serde::{de, Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::Debug;
 use std::marker::PhantomData;
@@ -39,6 +47,55 @@ pub struct EncodingConfigWithDefault<E: Default + PartialEq> {
         skip_serializing_if = "crate::serde::skip_serializing_if_default"
     )]
     pub(super) timestamp_format: Option<TimestampFormat>,
+    /// Encrypt the following fields before the sink serializes the event,
+    /// replacing each one with an envelope-encrypted object. (See `encryption`.)
+    #[serde(
+        default,
+        skip_serializing_if = "crate::serde::skip_serializing_if_default"
+    )]
+    pub(super) encrypt_fields: Option<Vec<Atom>>,
+    /// The envelope encryption scheme used for `encrypt_fields`. Required if
+    /// `encrypt_fields` is set.
+    #[serde(
+        default,
+        skip_serializing_if = "crate::serde::skip_serializing_if_default"
+    )]
+    pub(super) encryption: Option<EncryptionConfig>,
+    /// Maximum nesting depth the streaming JSON encoder will descend into
+    /// before returning an encoding error, guarding against a stack overflow
+    /// on a pathologically deep event. Defaults to `DEFAULT_JSON_DEPTH_LIMIT`.
+    #[serde(
+        default,
+        skip_serializing_if = "crate::serde::skip_serializing_if_default"
+    )]
+    pub(super) json_depth_limit: Option<usize>,
+    /// Whether the streaming JSON encoder emits compact or pretty-printed
+    /// output. Only consulted by codecs that encode to JSON.
+    #[serde(
+        default,
+        skip_serializing_if = "crate::serde::skip_serializing_if_default"
+    )]
+    pub(super) json_format: JsonFormat,
+    /// The wire format `encode` serializes the (filtered, possibly
+    /// encrypted) event into.
+    #[serde(
+        default,
+        skip_serializing_if = "crate::serde::skip_serializing_if_default"
+    )]
+    pub(super) format: WireFormat,
+}
+
+/// The wire format used to serialize an event, independent of the sink's own
+/// `codec` (which governs things like framing, not byte-level encoding).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WireFormat {
+    #[default]
+    Json,
+    /// Binary CBOR encoding. Unlike `Json`, preserves the `Value::Integer`
+    /// vs `Value::Float` distinction and writes `Value::Bytes` as a native
+    /// byte string rather than base64 text.
+    Cbor,
 }
 
 impl<E: Default + PartialEq> EncodingConfiguration<E> for EncodingConfigWithDefault<E> {
@@ -63,6 +120,12 @@ impl<E: Default + PartialEq> EncodingConfiguration<E> for EncodingConfigWithDefa
     fn set_timestamp_format(&mut self, format: Option<TimestampFormat>) -> Option<TimestampFormat> {
         std::mem::replace(&mut self.timestamp_format, format)
     }
+    fn encrypt_fields(&self) -> &Option<Vec<Atom>> {
+        &self.encrypt_fields
+    }
+    fn set_encrypt_fields(&mut self, fields: Option<Vec<Atom>>) -> Option<Vec<Atom>> {
+        std::mem::replace(&mut self.encrypt_fields, fields)
+    }
 }
 
 impl<E> EncodingConfigWithDefault<E>
@@ -78,38 +141,219 @@ where
             only_fields: self.only_fields,
             except_fields: self.except_fields,
             timestamp_format: self.timestamp_format,
+            encrypt_fields: self.encrypt_fields,
+            encryption: self.encryption,
+            json_depth_limit: self.json_depth_limit,
+            json_format: self.json_format,
+            format: self.format,
         }
     }
-    pub(crate) fn without_default<X>(self) -> EncodingConfig<X>
+    /// Converts to the plain `EncodingConfig<X>`, which has no field to
+    /// carry `encrypt_fields`/`encryption` or a non-default `format`.
+    ///
+    /// Errors instead of silently dropping them: a sink that resolved its
+    /// config through this conversion must not end up shipping the
+    /// selected fields in plaintext, or a non-JSON `format` silently
+    /// falling back to JSON, just because `EncodingConfig` can't represent
+    /// them.
+    pub(crate) fn without_default<X>(self) -> Result<EncodingConfig<X>, LossyConversionError>
     where
         X: From<E> + PartialEq,
     {
-        EncodingConfig {
+        if self
+            .encrypt_fields
+            .as_ref()
+            .map_or(false, |fields| !fields.is_empty())
+        {
+            return Err(LossyConversionError::EncryptFields);
+        }
+        if self.format != WireFormat::Json {
+            return Err(LossyConversionError::Format);
+        }
+
+        Ok(EncodingConfig {
             codec: self.codec.into(),
             only_fields: self.only_fields,
             except_fields: self.except_fields,
             timestamp_format: self.timestamp_format,
+        })
+    }
+
+    /// Encrypts the fields named in `encrypt_fields`, in place, replacing
+    /// each selected field's value with its envelope-encrypted form.
+    ///
+    /// A single `BatchKey` is generated (or derived from the configured
+    /// password) and reused for every selected field, so the cost of
+    /// generating the AES key and wrapping it for each recipient is paid
+    /// once per event rather than once per field.
+    pub(crate) fn encrypt_fields_into(
+        &self,
+        fields: &mut BTreeMap<Atom, Value>,
+    ) -> Result<(), super::encryption::EncryptionError> {
+        let selected = match &self.encrypt_fields {
+            Some(selected) if !selected.is_empty() => selected,
+            _ => return Ok(()),
+        };
+        let encryption = match &self.encryption {
+            Some(encryption) => encryption,
+            None => return Err(super::encryption::EncryptionError::NoKeyMaterial),
+        };
+
+        let batch_key = BatchKey::generate(encryption)?;
+
+        for field in selected {
+            if let Some(value) = fields.get(field) {
+                let plaintext = serde_json::to_vec(value)
+                    .map_err(super::encryption::EncryptionError::Serialize)?;
+                let envelope = batch_key.seal(&plaintext)?;
+                let envelope_value = serde_json::to_value(&envelope)
+                    .map_err(super::encryption::EncryptionError::Serialize)?;
+                fields.insert(field.clone(), Value::from(envelope_value));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The recursion depth at which the streaming JSON encoder gives up,
+    /// falling back to `DEFAULT_JSON_DEPTH_LIMIT` if unset.
+    pub(crate) fn json_depth_limit(&self) -> usize {
+        self.json_depth_limit.unwrap_or(DEFAULT_JSON_DEPTH_LIMIT)
+    }
+
+    /// Whether the streaming JSON encoder should emit compact or
+    /// pretty-printed output.
+    pub(crate) fn json_format(&self) -> JsonFormat {
+        self.json_format
+    }
+
+    /// Encodes `fields` into `out` using this config's `format`.
+    ///
+    /// `encrypt_fields` (if set) is applied first, replacing each selected
+    /// field's plaintext value with its envelope-encrypted form, before the
+    /// (possibly now-encrypted) fields are written in the wire format
+    /// requested by `format`.
+    pub(crate) fn encode(
+        &self,
+        fields: &BTreeMap<Atom, Value>,
+        out: &mut BytesMut,
+    ) -> Result<(), EncodeError> {
+        match self.format {
+            WireFormat::Json => self.encode_json(fields, out),
+            WireFormat::Cbor => self.encode_cbor(fields, out),
+        }
+    }
+
+    /// Encodes `fields` as JSON into `out`.
+    ///
+    /// `encrypt_fields` (if set) is applied first, replacing each selected
+    /// field's plaintext value with its envelope-encrypted form, before the
+    /// (possibly now-encrypted) fields are written via the streaming
+    /// encoder using this config's `json_depth_limit`/`json_format`.
+    pub(crate) fn encode_json(
+        &self,
+        fields: &BTreeMap<Atom, Value>,
+        out: &mut BytesMut,
+    ) -> Result<(), EncodeError> {
+        let mut fields = fields.clone();
+        self.encrypt_fields_into(&mut fields)?;
+        encode_streaming(&fields, out, self.json_depth_limit(), self.json_format())?;
+        Ok(())
+    }
+
+    /// Encodes `fields` as CBOR into `out`.
+    ///
+    /// `encrypt_fields` is applied first, exactly as in `encode_json`, so
+    /// the CBOR codec gets the same field-privacy and encryption behavior
+    /// rather than a second, divergent implementation.
+    pub(crate) fn encode_cbor(
+        &self,
+        fields: &BTreeMap<Atom, Value>,
+        out: &mut BytesMut,
+    ) -> Result<(), EncodeError> {
+        let mut fields = fields.clone();
+        self.encrypt_fields_into(&mut fields)?;
+        super::cbor_encoder::encode_cbor(&fields, out, self.timestamp_format)?;
+        Ok(())
+    }
+}
+
+/// Errors that can occur while applying `encrypt_fields` and then encoding
+/// the (possibly now-encrypted) event.
+#[derive(Debug)]
+pub(crate) enum EncodeError {
+    Encryption(super::encryption::EncryptionError),
+    Json(JsonEncodingError),
+    Cbor(CborEncodingError),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Encryption(e) => write!(f, "{}", e),
+            Self::Json(e) => write!(f, "{}", e),
+            Self::Cbor(e) => write!(f, "{}", e),
         }
     }
 }
 
-impl<E> Into<EncodingConfig<E>> for EncodingConfigWithDefault<E>
+impl std::error::Error for EncodeError {}
+
+impl From<super::encryption::EncryptionError> for EncodeError {
+    fn from(e: super::encryption::EncryptionError) -> Self {
+        Self::Encryption(e)
+    }
+}
+
+impl From<JsonEncodingError> for EncodeError {
+    fn from(e: JsonEncodingError) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<CborEncodingError> for EncodeError {
+    fn from(e: CborEncodingError) -> Self {
+        Self::Cbor(e)
+    }
+}
+
+/// Returned by `without_default`/`TryFrom<EncodingConfigWithDefault<E>>` when
+/// the source config set a field that `EncodingConfig` has no room to carry.
+#[derive(Debug)]
+pub(crate) enum LossyConversionError {
+    /// `encrypt_fields` was set; converting would ship those fields in
+    /// plaintext instead of encrypted.
+    EncryptFields,
+    /// `format` wasn't the default (`Json`); converting would silently
+    /// fall back to JSON instead of the requested wire format.
+    Format,
+}
+
+impl fmt::Display for LossyConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EncryptFields => write!(
+                f,
+                "cannot drop `encrypt_fields` during conversion: the selected fields would be shipped in plaintext"
+            ),
+            Self::Format => write!(
+                f,
+                "cannot drop a non-default `format` during conversion: the event would silently fall back to JSON"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LossyConversionError {}
+
+impl<E> std::convert::TryFrom<EncodingConfigWithDefault<E>> for EncodingConfig<E>
 where
     E: Default + PartialEq,
 {
-    fn into(self) -> EncodingConfig<E> {
-        let Self {
-            codec,
-            only_fields,
-            except_fields,
-            timestamp_format,
-        } = self;
-        EncodingConfig {
-            codec,
-            only_fields,
-            except_fields,
-            timestamp_format,
-        }
+    type Error = LossyConversionError;
+
+    fn try_from(value: EncodingConfigWithDefault<E>) -> Result<Self, Self::Error> {
+        value.without_default()
     }
 }
 
@@ -120,6 +364,11 @@ impl<E: Default + PartialEq> From<E> for EncodingConfigWithDefault<E> {
             only_fields: Default::default(),
             except_fields: Default::default(),
             timestamp_format: Default::default(),
+            encrypt_fields: Default::default(),
+            encryption: Default::default(),
+            json_depth_limit: Default::default(),
+            json_format: Default::default(),
+            format: Default::default(),
         }
     }
 }
@@ -162,6 +411,11 @@ where
                     only_fields: Default::default(),
                     except_fields: Default::default(),
                     timestamp_format: Default::default(),
+                    encrypt_fields: Default::default(),
+                    encryption: Default::default(),
+                    json_depth_limit: Default::default(),
+                    json_format: Default::default(),
+                    format: Default::default(),
                 })
             }
 
@@ -184,6 +438,11 @@ where
             only_fields: inner.only_fields,
             except_fields: inner.except_fields,
             timestamp_format: inner.timestamp_format,
+            encrypt_fields: inner.encrypt_fields,
+            encryption: inner.encryption,
+            json_depth_limit: inner.json_depth_limit,
+            json_format: inner.json_format,
+            format: inner.format,
         };
 
         concrete
@@ -192,3 +451,155 @@ where
         Ok(concrete)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_encode_json_encrypts_selected_fields() {
+        let mut fields = BTreeMap::new();
+        fields.insert(Atom::from("message"), Value::Bytes(Bytes::from("secret")));
+        fields.insert(Atom::from("host"), Value::Integer(1));
+
+        let config = EncodingConfigWithDefault::<()> {
+            codec: (),
+            only_fields: None,
+            except_fields: None,
+            timestamp_format: None,
+            encrypt_fields: Some(vec![Atom::from("message")]),
+            encryption: Some(EncryptionConfig {
+                recipients: Vec::new(),
+                password: Some("hunter2".into()),
+            }),
+            json_depth_limit: None,
+            json_format: JsonFormat::Compact,
+            format: WireFormat::Json,
+        };
+
+        let mut out = BytesMut::new();
+        config
+            .encode_json(&fields, &mut out)
+            .expect("encode failed");
+
+        let encoded: serde_json::Value = serde_json::from_slice(&out).expect("output wasn't JSON");
+
+        // The selected field is now an envelope object, not the plaintext value.
+        assert!(encoded["message"]["ciphertext"].is_string());
+        assert!(encoded["message"]["alg"].is_string());
+        assert!(encoded["message"]["salt"].is_string());
+        assert!(encoded["message"].get("keys").is_none());
+
+        // Fields not named in `encrypt_fields` are untouched.
+        assert_eq!(encoded["host"], serde_json::json!(1));
+    }
+
+    #[test]
+    fn test_encode_dispatches_to_cbor() {
+        let mut fields = BTreeMap::new();
+        fields.insert(Atom::from("count"), Value::Integer(3));
+        fields.insert(Atom::from("ratio"), Value::Float(1.5));
+        fields.insert(
+            Atom::from("payload"),
+            Value::Bytes(Bytes::from_static(b"\x00\x01\xff")),
+        );
+
+        let config = EncodingConfigWithDefault::<()> {
+            codec: (),
+            only_fields: None,
+            except_fields: None,
+            timestamp_format: None,
+            encrypt_fields: None,
+            encryption: None,
+            json_depth_limit: None,
+            json_format: JsonFormat::Compact,
+            format: WireFormat::Cbor,
+        };
+
+        let mut out = BytesMut::new();
+        config.encode(&fields, &mut out).expect("encode failed");
+
+        let decoded: serde_cbor::value::Value =
+            serde_cbor::from_slice(&out).expect("output wasn't CBOR");
+        let map = match decoded {
+            serde_cbor::value::Value::Map(map) => map,
+            other => panic!("expected a CBOR map, got {:?}", other),
+        };
+        let get = |key: &str| {
+            map.get(&serde_cbor::value::Value::Text(key.to_owned()))
+                .unwrap_or_else(|| panic!("missing key {}", key))
+        };
+
+        // CBOR, unlike JSON, keeps integers and floats as distinct types.
+        assert!(matches!(get("count"), serde_cbor::value::Value::Integer(3)));
+        assert!(
+            matches!(get("ratio"), serde_cbor::value::Value::Float(f) if (*f - 1.5).abs() < f64::EPSILON)
+        );
+        assert!(matches!(
+            get("payload"),
+            serde_cbor::value::Value::Bytes(b) if b == &[0x00, 0x01, 0xff]
+        ));
+    }
+
+    #[test]
+    fn test_without_default_rejects_encrypt_fields() {
+        let config = EncodingConfigWithDefault::<()> {
+            codec: (),
+            only_fields: None,
+            except_fields: None,
+            timestamp_format: None,
+            encrypt_fields: Some(vec![Atom::from("message")]),
+            encryption: Some(EncryptionConfig {
+                recipients: Vec::new(),
+                password: Some("hunter2".into()),
+            }),
+            json_depth_limit: None,
+            json_format: JsonFormat::Compact,
+            format: WireFormat::Json,
+        };
+
+        assert!(matches!(
+            config.without_default::<()>(),
+            Err(LossyConversionError::EncryptFields)
+        ));
+    }
+
+    #[test]
+    fn test_without_default_rejects_non_default_format() {
+        let config = EncodingConfigWithDefault::<()> {
+            codec: (),
+            only_fields: None,
+            except_fields: None,
+            timestamp_format: None,
+            encrypt_fields: None,
+            encryption: None,
+            json_depth_limit: None,
+            json_format: JsonFormat::Compact,
+            format: WireFormat::Cbor,
+        };
+
+        assert!(matches!(
+            config.without_default::<()>(),
+            Err(LossyConversionError::Format)
+        ));
+    }
+
+    #[test]
+    fn test_without_default_passes_through_plain_config() {
+        let config = EncodingConfigWithDefault::<()> {
+            codec: (),
+            only_fields: Some(vec![Atom::from("message")]),
+            except_fields: None,
+            timestamp_format: None,
+            encrypt_fields: None,
+            encryption: None,
+            json_depth_limit: None,
+            json_format: JsonFormat::Compact,
+            format: WireFormat::Json,
+        };
+
+        let converted = config.without_default::<()>().expect("should not be lossy");
+        assert_eq!(converted.only_fields, Some(vec![Atom::from("message")]));
+    }
+}
@@ -0,0 +1,283 @@
+use base64::encode as base64_encode;
+use rand::{rngs::OsRng, RngCore};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{PaddingScheme, PublicKey, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use aes_gcm::aead::{Aead, NewAead};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+
+const AES_KEY_LEN: usize = 32;
+const AES_NONCE_LEN: usize = 12;
+const SCRYPT_SALT_LEN: usize = 16;
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+const ALG_RSA_OAEP: &str = "AES-256-GCM+RSA-OAEP";
+const ALG_SCRYPT: &str = "AES-256-GCM+scrypt";
+
+/// Configuration for field-level envelope encryption.
+///
+/// Exactly one of `recipients` or `password` must be set: `recipients` wraps
+/// a fresh per-batch AES key for each RSA public key so that several
+/// downstream consumers can decrypt independently, while `password` derives
+/// the AES key from a shared secret via scrypt. Validated at deserialize
+/// time via `validate`, so a config with both (or neither) set is rejected
+/// up front rather than failing lazily on the first encrypted event.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(try_from = "RawEncryptionConfig")]
+pub struct EncryptionConfig {
+    /// PEM-encoded RSA public keys of the recipients allowed to unwrap the
+    /// per-batch AES key. Mutually exclusive with `password`.
+    pub recipients: Vec<String>,
+    /// A shared password used to derive the AES key via scrypt. Mutually
+    /// exclusive with `recipients`.
+    pub password: Option<String>,
+}
+
+impl EncryptionConfig {
+    /// Checks that exactly one of `recipients`/`password` is set.
+    pub fn validate(&self) -> Result<(), EncryptionError> {
+        match (self.recipients.is_empty(), self.password.is_some()) {
+            (false, true) => Err(EncryptionError::AmbiguousKeyMaterial),
+            (true, false) => Err(EncryptionError::NoKeyMaterial),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Mirrors `EncryptionConfig`'s wire format; exists only so deserialization
+/// can run through `TryFrom` and reject an invalid config up front.
+#[derive(Deserialize)]
+pub(crate) struct RawEncryptionConfig {
+    #[serde(default)]
+    recipients: Vec<String>,
+    #[serde(default)]
+    password: Option<String>,
+}
+
+impl std::convert::TryFrom<RawEncryptionConfig> for EncryptionConfig {
+    type Error = EncryptionError;
+
+    fn try_from(raw: RawEncryptionConfig) -> Result<Self, Self::Error> {
+        let config = Self {
+            recipients: raw.recipients,
+            password: raw.password,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// The result of encrypting a single field's value.
+///
+/// Serializes to the envelope object that replaces the plaintext field:
+/// `{ "alg": ..., "nonce": ..., "ciphertext": ..., "keys": [...] }` in recipient
+/// mode, or `{ "alg": ..., "nonce": ..., "ciphertext": ..., "salt": ...,
+/// "scrypt_params": {...} }` in password mode.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Envelope {
+    /// The AEAD algorithm used to encrypt `ciphertext`.
+    pub alg: &'static str,
+    /// Base64-encoded AES-GCM nonce.
+    pub nonce: String,
+    /// Base64-encoded AES-GCM ciphertext (including the auth tag) of the
+    /// JSON-serialized field value.
+    pub ciphertext: String,
+    /// Base64-encoded copies of the AES key, one per recipient, each wrapped
+    /// with that recipient's RSA-OAEP public key. Empty in password mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<String>,
+    /// Base64-encoded scrypt salt, present only in password mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+    /// The scrypt cost parameters (`log2(N)`, `r`, `p`) used to derive the
+    /// AES key, present only in password mode and required to decrypt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrypt_params: Option<ScryptParams>,
+}
+
+/// The scrypt cost parameters used to derive an AES key from a password.
+/// See `scrypt::Params` for their meaning.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScryptParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+#[derive(Debug)]
+pub enum EncryptionError {
+    InvalidRecipientKey(rsa::pkcs8::spki::Error),
+    KeyWrap(rsa::errors::Error),
+    Cipher(aes_gcm::Error),
+    KeyDerivation(scrypt::errors::InvalidParams),
+    Serialize(serde_json::Error),
+    NoKeyMaterial,
+    AmbiguousKeyMaterial,
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidRecipientKey(e) => write!(f, "invalid recipient public key: {}", e),
+            Self::KeyWrap(e) => write!(f, "failed to wrap AES key for recipient: {}", e),
+            Self::Cipher(e) => write!(f, "AES-GCM encryption failed: {}", e),
+            Self::KeyDerivation(e) => write!(f, "scrypt key derivation failed: {}", e),
+            Self::Serialize(e) => write!(f, "failed to serialize field value: {}", e),
+            Self::NoKeyMaterial => {
+                write!(f, "encryption requires either `recipients` or `password`")
+            }
+            Self::AmbiguousKeyMaterial => write!(
+                f,
+                "encryption accepts either `recipients` or `password`, not both"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+/// A single-use AES-256-GCM session key generated for one batch.
+///
+/// Reused across every selected field of the batch so that the (comparatively
+/// expensive) RSA wrapping only happens once per recipient per batch.
+pub struct BatchKey {
+    key_bytes: [u8; AES_KEY_LEN],
+    alg: &'static str,
+    wrapped_keys: Vec<String>,
+    salt: Option<String>,
+    scrypt_params: Option<ScryptParams>,
+}
+
+impl BatchKey {
+    /// Generates a fresh AES key for the batch and wraps it for every
+    /// configured recipient (or derives it from the configured password).
+    pub fn generate(config: &EncryptionConfig) -> Result<Self, EncryptionError> {
+        config.validate()?;
+
+        if let Some(password) = &config.password {
+            return Self::from_password(password);
+        }
+
+        let mut key_bytes = [0u8; AES_KEY_LEN];
+        OsRng.fill_bytes(&mut key_bytes);
+
+        let wrapped_keys = config
+            .recipients
+            .iter()
+            .map(|pem| wrap_key_for_recipient(&key_bytes, pem))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            key_bytes,
+            alg: ALG_RSA_OAEP,
+            wrapped_keys,
+            salt: None,
+            scrypt_params: None,
+        })
+    }
+
+    fn from_password(password: &str) -> Result<Self, EncryptionError> {
+        let mut salt = [0u8; SCRYPT_SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+
+        let scrypt_params = ScryptParams {
+            log_n: SCRYPT_LOG_N,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+        };
+        let params = scrypt::Params::new(scrypt_params.log_n, scrypt_params.r, scrypt_params.p)
+            .map_err(EncryptionError::KeyDerivation)?;
+        let mut key_bytes = [0u8; AES_KEY_LEN];
+        scrypt::scrypt(password.as_bytes(), &salt, &params, &mut key_bytes)
+            .expect("scrypt output buffer is statically sized to AES_KEY_LEN");
+
+        Ok(Self {
+            key_bytes,
+            alg: ALG_SCRYPT,
+            wrapped_keys: Vec::new(),
+            salt: Some(base64_encode(salt)),
+            scrypt_params: Some(scrypt_params),
+        })
+    }
+
+    /// Encrypts a single field's JSON-serialized value under this batch's key.
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Envelope, EncryptionError> {
+        let mut nonce_bytes = [0u8; AES_NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce_bytes);
+
+        let cipher = Aes256Gcm::new(Key::from_slice(&self.key_bytes));
+        let nonce = Nonce::from_slice(&nonce_bytes);
+        let ciphertext = cipher
+            .encrypt(nonce, plaintext)
+            .map_err(EncryptionError::Cipher)?;
+
+        Ok(Envelope {
+            alg: self.alg,
+            nonce: base64_encode(nonce_bytes),
+            ciphertext: base64_encode(ciphertext),
+            keys: self.wrapped_keys.clone(),
+            salt: self.salt.clone(),
+            scrypt_params: self.scrypt_params,
+        })
+    }
+}
+
+fn wrap_key_for_recipient(key_bytes: &[u8], pem: &str) -> Result<String, EncryptionError> {
+    let public_key =
+        RsaPublicKey::from_public_key_pem(pem).map_err(EncryptionError::InvalidRecipientKey)?;
+    let padding = PaddingScheme::new_oaep::<sha2::Sha256>();
+    let wrapped = public_key
+        .encrypt(&mut OsRng, padding, key_bytes)
+        .map_err(EncryptionError::KeyWrap)?;
+    Ok(base64_encode(wrapped))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_rejects_neither_set() {
+        let config = EncryptionConfig::default();
+        assert!(matches!(
+            config.validate(),
+            Err(EncryptionError::NoKeyMaterial)
+        ));
+    }
+
+    #[test]
+    fn test_validate_rejects_both_set() {
+        let config = EncryptionConfig {
+            recipients: vec!["not-really-a-pem".to_owned()],
+            password: Some("hunter2".to_owned()),
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(EncryptionError::AmbiguousKeyMaterial)
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_rejects_ambiguous_config_up_front() {
+        let result: Result<EncryptionConfig, _> =
+            serde_json::from_value(serde_json::json!({
+                "recipients": ["not-really-a-pem"],
+                "password": "hunter2",
+            }));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_accepts_password_only() {
+        let config: EncryptionConfig =
+            serde_json::from_value(serde_json::json!({ "password": "hunter2" })).unwrap();
+
+        assert_eq!(config.password.as_deref(), Some("hunter2"));
+        assert!(config.recipients.is_empty());
+    }
+}
@@ -0,0 +1,253 @@
+use bytes::{BufMut, BytesMut};
+use serde::{Deserialize, Serialize};
+use serde_json::ser::{CompactFormatter, Formatter, PrettyFormatter};
+use std::io;
+
+/// Default nesting depth at which the streaming encoder gives up rather than
+/// risk overflowing the stack on an adversarial or pathological event.
+pub const DEFAULT_JSON_DEPTH_LIMIT: usize = 64;
+
+#[derive(Debug)]
+pub enum JsonEncodingError {
+    /// The event nested maps/arrays deeper than the configured depth limit.
+    DepthLimitExceeded { limit: usize },
+    Io(io::Error),
+    Serde(serde_json::Error),
+}
+
+impl std::fmt::Display for JsonEncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DepthLimitExceeded { limit } => {
+                write!(f, "event exceeded the JSON recursion depth limit of {}", limit)
+            }
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Serde(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for JsonEncodingError {}
+
+/// Writes into a `BufMut` as if it were a `std::io::Write`, so serde_json's
+/// `Serializer` can serialize directly into the sink's output buffer instead
+/// of through an intermediate `String`/`Vec<u8>`.
+struct BufMutWriter<'a, B>(&'a mut B);
+
+impl<'a, B: BufMut> io::Write for BufMutWriter<'a, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.put_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A no-op `Write` that only tallies how many bytes would have been written.
+///
+/// Used for a first, size-counting pass over the event so the real encode
+/// can `reserve` the output `BytesMut` up front instead of growing (and
+/// re-copying) it incrementally.
+#[derive(Default)]
+pub struct CountingWriter {
+    len: usize,
+}
+
+impl CountingWriter {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl io::Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct DepthLimitMarker(usize);
+
+impl std::fmt::Display for DepthLimitMarker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "JSON recursion depth limit of {} exceeded", self.0)
+    }
+}
+
+impl std::error::Error for DepthLimitMarker {}
+
+fn depth_limit_error(limit: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, DepthLimitMarker(limit))
+}
+
+/// Wraps a serde_json `Formatter` with a recursion-depth guard so that
+/// arbitrarily (or maliciously) deep maps/arrays fail with an encoding error
+/// instead of overflowing the stack.
+struct DepthLimitedFormatter<F> {
+    inner: F,
+    depth: usize,
+    limit: usize,
+}
+
+impl<F> DepthLimitedFormatter<F> {
+    fn new(inner: F, limit: usize) -> Self {
+        Self {
+            inner,
+            depth: 0,
+            limit,
+        }
+    }
+
+    fn enter<W: io::Write>(&mut self) -> io::Result<()> {
+        self.depth += 1;
+        if self.depth > self.limit {
+            return Err(depth_limit_error(self.limit));
+        }
+        Ok(())
+    }
+
+    fn exit(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+macro_rules! forward {
+    ($name:ident(&mut self $(, $arg:ident: $ty:ty)*)) => {
+        fn $name<W: ?Sized + io::Write>(&mut self, writer: &mut W $(, $arg: $ty)*) -> io::Result<()> {
+            self.inner.$name(writer $(, $arg)*)
+        }
+    };
+}
+
+impl<F: Formatter> Formatter for DepthLimitedFormatter<F> {
+    forward!(write_null(&mut self));
+    forward!(write_bool(&mut self, value: bool));
+    forward!(write_i64(&mut self, value: i64));
+    forward!(write_u64(&mut self, value: u64));
+    forward!(write_f64(&mut self, value: f64));
+    forward!(write_string_fragment(&mut self, fragment: &str));
+    forward!(begin_string(&mut self));
+    forward!(end_string(&mut self));
+
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.enter::<W>()?;
+        self.inner.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.exit();
+        self.inner.end_array(writer)
+    }
+
+    forward!(begin_array_value(&mut self, first: bool));
+    forward!(end_array_value(&mut self));
+
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.enter::<W>()?;
+        self.inner.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.exit();
+        self.inner.end_object(writer)
+    }
+
+    forward!(begin_object_key(&mut self, first: bool));
+    forward!(end_object_key(&mut self));
+    forward!(begin_object_value(&mut self));
+    forward!(end_object_value(&mut self));
+}
+
+/// Whether the streaming JSON encoder emits compact or pretty-printed output.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonFormat {
+    #[default]
+    Compact,
+    Pretty,
+}
+
+/// Serializes `value` directly into `out`, without building an intermediate
+/// `String`/`serde_json::Value` tree.
+///
+/// A first pass over `value` with a byte-counting writer reserves `out`'s
+/// capacity up front, so the real encode only ever appends.
+pub fn encode_streaming<T: Serialize>(
+    value: &T,
+    out: &mut BytesMut,
+    depth_limit: usize,
+    format: JsonFormat,
+) -> Result<(), JsonEncodingError> {
+    let mut counter = CountingWriter::default();
+    write_with_formatter(value, &mut counter, depth_limit, format)?;
+    out.reserve(counter.len());
+
+    write_with_formatter(value, &mut BufMutWriter(out), depth_limit, format)
+}
+
+fn write_with_formatter<T: Serialize, W: io::Write>(
+    value: &T,
+    writer: W,
+    depth_limit: usize,
+    format: JsonFormat,
+) -> Result<(), JsonEncodingError> {
+    let result = match format {
+        JsonFormat::Compact => {
+            let formatter = DepthLimitedFormatter::new(CompactFormatter, depth_limit);
+            let mut ser = serde_json::Serializer::with_formatter(writer, formatter);
+            value.serialize(&mut ser)
+        }
+        JsonFormat::Pretty => {
+            let formatter = DepthLimitedFormatter::new(PrettyFormatter::new(), depth_limit);
+            let mut ser = serde_json::Serializer::with_formatter(writer, formatter);
+            value.serialize(&mut ser)
+        }
+    };
+
+    result.map_err(|e| {
+        if e.is_io() && e.to_string().contains("recursion depth limit") {
+            JsonEncodingError::DepthLimitExceeded { limit: depth_limit }
+        } else {
+            JsonEncodingError::Serde(e)
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_encode_streaming_compact() {
+        let mut out = BytesMut::new();
+        encode_streaming(&json!({"a": [1, 2, 3]}), &mut out, DEFAULT_JSON_DEPTH_LIMIT, JsonFormat::Compact)
+            .expect("encode failed");
+
+        assert_eq!(&out[..], br#"{"a":[1,2,3]}"#);
+    }
+
+    #[test]
+    fn test_encode_streaming_rejects_pathological_depth() {
+        // Nest arrays 8 levels deep, twice the limit of 4 used below.
+        let mut value = serde_json::Value::Null;
+        for _ in 0..8 {
+            value = serde_json::Value::Array(vec![value]);
+        }
+
+        let mut out = BytesMut::new();
+        let result = encode_streaming(&value, &mut out, 4, JsonFormat::Compact);
+
+        assert!(matches!(
+            result,
+            Err(JsonEncodingError::DepthLimitExceeded { limit: 4 })
+        ));
+    }
+}